@@ -0,0 +1,59 @@
+// Compares the ways a scanner can get its keyword table, both building the
+// scanner on its own and scanning keyword-heavy source with it, and times
+// single-character token dispatch on punctuation-heavy lines. Run with
+// `cargo bench`.
+use parser::{Scanner, KEYWORDS};
+use std::{hint::black_box, time::Instant};
+
+const ITERATIONS: u32 = 100;
+
+const SNIPPET: &str = "class Counter { fun add(n) { if (n > 0 and this.total != nil) { return this.total + n; } else { print false; } } }\n\
+for (var i = 0; i < 10; i = i + 1) { while (true or false) { scope { var x = super.count(i); } } }\n";
+
+// short, so each scan is dominated by dispatching tokens rather than by
+// slicing lexemes out of a long source
+const PUNCTUATION_LINE: &str = "f(a,b);{x=(a+b)*(c-d)/e;}{g(-h,i+j);}(k*l)/(m-n);";
+const PUNCTUATION_LINES: usize = 100;
+
+fn time(name: &str, mut run: impl FnMut()) {
+    run();
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        run();
+    }
+    let per_iteration = start.elapsed() / ITERATIONS;
+
+    println!("{:<40} {:>12?}", name, per_iteration);
+}
+
+fn construct(build: fn(String) -> Scanner) -> impl FnMut() {
+    move || { black_box(build(black_box(String::new()))); }
+}
+
+fn scan(build: fn(String) -> Scanner, source: &str) -> impl FnMut() + '_ {
+    move || {
+        let mut scanner = build(source.to_string());
+        black_box(scanner.scan_tokens().len());
+    }
+}
+
+fn main() {
+    let source = SNIPPET.repeat(20);
+    let from_slice: fn(String) -> Scanner = |source| Scanner::with_keywords_from_slice(source, KEYWORDS);
+
+    time("construct: new", construct(Scanner::new));
+    time("construct: with_keywords_from_slice", construct(from_slice));
+    time("construct: with_phf_keywords", construct(Scanner::with_phf_keywords));
+
+    time("scan: new", scan(Scanner::new, &source));
+    time("scan: with_keywords_from_slice", scan(from_slice, &source));
+    time("scan: with_phf_keywords", scan(Scanner::with_phf_keywords, &source));
+
+    time("scan: punctuation-heavy lines", || {
+        for _ in 0..PUNCTUATION_LINES {
+            let mut scanner = Scanner::new(black_box(PUNCTUATION_LINE).to_string());
+            black_box(scanner.scan_tokens().len());
+        }
+    });
+}
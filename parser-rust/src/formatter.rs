@@ -0,0 +1,145 @@
+use crate::{Token, TokenType};
+
+const INDENT: &str = "    ";
+
+// Works from token types alone, so it can format source that would not
+// parse. Comments are not tokens and so do not survive formatting.
+pub struct Formatter;
+
+impl Formatter {
+    pub fn format_token_stream(tokens: &[Token]) -> String {
+        let mut output = String::new();
+        let mut indent: usize = 0;
+        let mut paren_depth: usize = 0;
+        let mut at_line_start = true;
+        let mut prev: Option<&TokenType> = None;
+        let mut prev_unary = false;
+
+        let tokens: Vec<&Token> = tokens.iter().filter(|token| !token.is_eof()).collect();
+
+        for (i, token) in tokens.iter().enumerate() {
+            let token_type = &token.token_type;
+
+            if *token_type == TokenType::RIGHTBRACE {
+                indent = indent.saturating_sub(1);
+
+                if !at_line_start {
+                    output.push('\n');
+                    at_line_start = true;
+                }
+            }
+
+            if at_line_start {
+                output.push_str(&INDENT.repeat(indent));
+            }
+            else if Formatter::needs_space(prev, prev_unary, token_type) {
+                output.push(' ');
+            }
+
+            output.push_str(&token.lexeme);
+            at_line_start = false;
+
+            prev_unary = match token_type {
+                TokenType::BANG => true,
+                TokenType::MINUS => !prev.is_some_and(Formatter::ends_operand),
+                _ => false
+            };
+            prev = Some(token_type);
+
+            let line_break = match token_type {
+                TokenType::LEFTPAREN => { paren_depth += 1; false },
+                TokenType::RIGHTPAREN => { paren_depth = paren_depth.saturating_sub(1); false },
+                TokenType::LEFTBRACE => { indent += 1; true },
+                // a for loop's clauses stay on one line
+                TokenType::SEMICOLON => paren_depth == 0,
+                TokenType::RIGHTBRACE => !tokens.get(i + 1).is_some_and(|next| next.matches(TokenType::ELSE)),
+                _ => false
+            };
+
+            if line_break {
+                output.push('\n');
+                at_line_start = true;
+            }
+        }
+
+        if !at_line_start {
+            output.push('\n');
+        }
+
+        output
+    }
+
+    fn needs_space(prev: Option<&TokenType>, prev_unary: bool, current: &TokenType) -> bool {
+        let Some(prev) = prev else { return false; };
+
+        if prev_unary || matches!(prev, TokenType::LEFTPAREN | TokenType::DOT | TokenType::ELLIPSIS) {
+            return false;
+        }
+
+        match current {
+            TokenType::RIGHTPAREN | TokenType::SEMICOLON | TokenType::COMMA | TokenType::DOT => false,
+            // calls hug their callee, but keywords like if and while keep a space
+            TokenType::LEFTPAREN => !Formatter::ends_operand(prev),
+            _ => true
+        }
+    }
+
+    fn ends_operand(token_type: &TokenType) -> bool {
+        matches!(token_type,
+            TokenType::IDENTIFIER | TokenType::NUMBER | TokenType::STRING |
+            TokenType::TRUE | TokenType::FALSE | TokenType::NIL |
+            TokenType::THIS | TokenType::SUPER | TokenType::RIGHTPAREN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scanner;
+
+    fn format(source: &str) -> String {
+        let mut scanner = Scanner::new(source.to_string());
+        Formatter::format_token_stream(scanner.scan_tokens())
+    }
+
+    #[test]
+    fn formats_valid_source() {
+        let formatted = format("var x=1;fun add(a,b){return a+b;}print add(x,2);");
+
+        assert_eq!(formatted, "var x = 1;\nfun add(a, b) {\n    return a + b;\n}\nprint add(x, 2);\n");
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn formats_source_with_syntax_error() {
+        assert_eq!(format("var = ;print (1 +;"), "var =;\nprint (1 +;\n");
+    }
+
+    #[test]
+    fn keeps_else_on_closing_brace_line() {
+        assert_eq!(format("if(a){print 1;}else{print 2;}"), "if (a) {\n    print 1;\n} else {\n    print 2;\n}\n");
+    }
+
+    #[test]
+    fn keeps_for_clauses_on_one_line() {
+        assert_eq!(format("for(var i=0;i<10;i=i+1){print i;}"), "for (var i = 0; i < 10; i = i + 1) {\n    print i;\n}\n");
+        assert_eq!(format("for(;;){}"), "for (;;) {\n}\n");
+    }
+
+    #[test]
+    fn distinguishes_unary_and_binary_minus() {
+        assert_eq!(format("var a=-1;var b=a-1;print -a- -b;print f(-x);print !done;"),
+            "var a = -1;\nvar b = a - 1;\nprint -a - -b;\nprint f(-x);\nprint !done;\n");
+    }
+
+    #[test]
+    fn tolerates_unbalanced_braces() {
+        assert_eq!(format("{{print 1;}"), "{\n    {\n        print 1;\n    }\n");
+        assert_eq!(format("}}print 1;{"), "}\n}\nprint 1;\n{\n");
+    }
+
+    #[test]
+    fn empty_input_formats_to_nothing() {
+        assert_eq!(format(""), "");
+    }
+}
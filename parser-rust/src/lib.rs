@@ -1,4 +1,12 @@
-use std::{any::Any, collections::HashMap, fmt::Display };
+use std::{collections::HashMap, fmt::Display, panic };
+
+mod formatter;
+mod repl;
+mod source_view;
+
+pub use formatter::Formatter;
+pub use repl::Repl;
+pub use source_view::SourceView;
 
 trait Substr {
     fn substr(&self, start: usize, end: usize) -> String;
@@ -10,7 +18,7 @@ impl Substr for String {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenType {
     LEFTPAREN, RIGHTPAREN, LEFTBRACE, RIGHTBRACE,
     COMMA, DOT, MINUS, PLUS, SEMICOLON, SLASH, STAR,
@@ -21,106 +29,485 @@ pub enum TokenType {
     GREATER, GREATEREQUAL,
     LESS, LESSEQUAL,
 
+    // Three character tokens.
+    ELLIPSIS,
+
     // Literals.
     IDENTIFIER, STRING, NUMBER,
 
     // Keywords.
     AND, CLASS, ELSE, FALSE, FUN, FOR, IF, NIL, OR,
-    PRINT, RETURN, SUPER, THIS, TRUE, VAR, WHILE,
+    PRINT, RETURN, SCOPE, SUPER, THIS, TRUE, VAR, WHILE,
 
     EOF
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum LiteralValue {
+    Nil,
+    Bool(bool),
+    Number(f64),
+    Str(String)
+}
+
+impl LiteralValue {
+    const ERROR_STR_LIMIT: usize = 20;
+
+    pub fn display_for_error(&self) -> String {
+        match self {
+            LiteralValue::Nil => String::from("nil"),
+            LiteralValue::Bool(b) => b.to_string(),
+            LiteralValue::Number(n) => n.to_string(),
+            LiteralValue::Str(s) => {
+                if s.chars().count() > Self::ERROR_STR_LIMIT {
+                    let truncated: String = s.chars().take(Self::ERROR_STR_LIMIT).collect();
+                    format!("\"{}...\"", truncated)
+                }
+                else {
+                    format!("\"{}\"", s)
+                }
+            }
+        }
+    }
+}
+
+// offset is in bytes from the start of the source
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+    pub offset: usize
+}
+
+// start is the token's first character, end is one past its last
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position
+}
+
+impl Span {
+    pub fn contains(&self, line: usize, column: usize) -> bool {
+        let position = (line, column);
+        (self.start.line, self.start.column) <= position && position < (self.end.line, self.end.column)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Token {
-    literal: Box<dyn Any>,
+    literal: LiteralValue,
     lexeme: String,
     line: usize,
+    span: Span,
+    // byte offset where the previous token ended
+    whitespace_start: usize,
     token_type: TokenType
 }
 
+impl Token {
+    pub fn literal(&self) -> &LiteralValue {
+        &self.literal
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn is_on_same_line_as(&self, other: &Token) -> bool {
+        self.span.start.line == other.span.start.line
+    }
+
+    // lines strictly between the end of the earlier token and the start of
+    // the later one, whichever order they are passed in
+    pub fn lines_between(&self, other: &Token) -> usize {
+        let (first, second) = if self.span.start.offset <= other.span.start.offset { (self, other) } else { (other, self) };
+        second.span.start.line.saturating_sub(first.span.end.line + 1)
+    }
+
+    pub fn preceding_whitespace<'a>(&self, source: &'a str) -> &'a str {
+        source.get(self.whitespace_start..self.span.start.offset).unwrap_or("")
+    }
+
+    pub fn is_eof(&self) -> bool {
+        self.matches(TokenType::EOF)
+    }
+
+    pub fn matches(&self, token_type: TokenType) -> bool {
+        self.token_type == token_type
+    }
+}
+
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Token Type: {:?},\nLine: {},\nLexeme: {}\nLiteral: {:?}\n", self.token_type, self.line, self.lexeme, self.literal)
     }
 }
 
+#[derive(Debug, PartialEq)]
+pub enum ScanError {
+    UnterminatedString,
+    InvalidLiteral(String),
+    InternalError(String)
+}
+
+impl Display for ScanError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScanError::UnterminatedString => write!(f, "Scanner error: unterminated string"),
+            ScanError::InvalidLiteral(literal) => write!(f, "Scanner error: invalid literal {}", literal),
+            ScanError::InternalError(message) => write!(f, "Scanner error: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for ScanError {}
+
+#[derive(Debug, Clone)]
+pub struct ScannerOptions {
+    // how many columns a '\t' advances the column counter by
+    pub tab_width: usize
+}
+
+impl Default for ScannerOptions {
+    fn default() -> ScannerOptions {
+        ScannerOptions { tab_width: 1 }
+    }
+}
+
+// the default keyword table, public so callers can extend it for
+// with_keywords_from_slice
+pub const KEYWORDS: &[(&str, TokenType)] = &[
+    ("and",    TokenType::AND),
+    ("class",  TokenType::CLASS),
+    ("else",   TokenType::ELSE),
+    ("false",  TokenType::FALSE),
+    ("for",    TokenType::FOR),
+    ("fun",    TokenType::FUN),
+    ("if",     TokenType::IF),
+    ("nil",    TokenType::NIL),
+    ("or",     TokenType::OR),
+    ("print",  TokenType::PRINT),
+    ("return", TokenType::RETURN),
+    ("scope",  TokenType::SCOPE),
+    ("super",  TokenType::SUPER),
+    ("this",   TokenType::THIS),
+    ("true",   TokenType::TRUE),
+    ("var",    TokenType::VAR),
+    ("while",  TokenType::WHILE),
+];
+
+// the same table hashed at compile time, for with_phf_keywords
+static PHF_KEYWORDS: phf::Map<&'static str, TokenType> = phf::phf_map! {
+    "and"    => TokenType::AND,
+    "class"  => TokenType::CLASS,
+    "else"   => TokenType::ELSE,
+    "false"  => TokenType::FALSE,
+    "for"    => TokenType::FOR,
+    "fun"    => TokenType::FUN,
+    "if"     => TokenType::IF,
+    "nil"    => TokenType::NIL,
+    "or"     => TokenType::OR,
+    "print"  => TokenType::PRINT,
+    "return" => TokenType::RETURN,
+    "scope"  => TokenType::SCOPE,
+    "super"  => TokenType::SUPER,
+    "this"   => TokenType::THIS,
+    "true"   => TokenType::TRUE,
+    "var"    => TokenType::VAR,
+    "while"  => TokenType::WHILE,
+};
+
+// tokens that are always a single character; the ones that need to peek at
+// the next character stay in scan_token's match
+static SINGLE_CHAR_TOKENS: phf::Map<char, TokenType> = phf::phf_map! {
+    '(' => TokenType::LEFTPAREN,
+    ')' => TokenType::RIGHTPAREN,
+    '{' => TokenType::LEFTBRACE,
+    '}' => TokenType::RIGHTBRACE,
+    ',' => TokenType::COMMA,
+    '-' => TokenType::MINUS,
+    '+' => TokenType::PLUS,
+    ';' => TokenType::SEMICOLON,
+    '/' => TokenType::SLASH,
+    '*' => TokenType::STAR,
+};
+
+enum Keywords {
+    Map(HashMap<String, TokenType>),
+    Phf(&'static phf::Map<&'static str, TokenType>)
+}
+
+impl Keywords {
+    fn from_slice(keywords: &[(&str, TokenType)]) -> Keywords {
+        Keywords::Map(keywords.iter()
+            .map(|(name, token_type)| (name.to_string(), token_type.clone()))
+            .collect())
+    }
+
+    fn get(&self, name: &str) -> Option<&TokenType> {
+        match self {
+            Keywords::Map(map) => map.get(name),
+            Keywords::Phf(map) => map.get(name)
+        }
+    }
+}
+
 pub struct Scanner{
     source: String,
+    // in chars, which is what current counts
+    source_len: usize,
+    options: ScannerOptions,
+    // every token scanned so far, whether by scan_tokens or next_token
     tokens: Vec<Token>,
-    keywords: HashMap<String, TokenType>,
+    // how many of those next_token has handed out
+    streamed: usize,
+    emitted_eof: bool,
+    keywords: Keywords,
     start: usize,
+    start_position: Position,
     current: usize,
-    line: usize
+    line: usize,
+    column: usize,
+    offset: usize,
+    last_token_end: usize
 }
 
 impl Scanner {
     pub fn new(source: String) -> Scanner {
+        Scanner::with_options(source, ScannerOptions::default())
+    }
+
+    pub fn with_options(source: String, options: ScannerOptions) -> Scanner {
+        Scanner::build(source, options, Keywords::from_slice(KEYWORDS))
+    }
+
+    pub fn with_keywords_from_slice(source: String, keywords: &[(&str, TokenType)]) -> Scanner {
+        Scanner::build(source, ScannerOptions::default(), Keywords::from_slice(keywords))
+    }
+
+    // looks keywords up in a table hashed at compile time, so building the
+    // scanner does not allocate one
+    pub fn with_phf_keywords(source: String) -> Scanner {
+        Scanner::build(source, ScannerOptions::default(), Keywords::Phf(&PHF_KEYWORDS))
+    }
+
+    fn build(source: String, options: ScannerOptions, keywords: Keywords) -> Scanner {
         Scanner {
+            source_len: source.chars().count(),
             source,
+            options,
             tokens: Vec::new(),
+            streamed: 0,
+            emitted_eof: false,
             start: 0,
+            start_position: Position { line: 1, column: 1, offset: 0 },
             current: 0,
-            line: 0,
-            keywords: Scanner::init_keywords()
+            line: 1,
+            column: 1,
+            offset: 0,
+            last_token_end: 0,
+            keywords
         }
     }
 
-    fn init_keywords() -> HashMap<String, TokenType> {
-        let mut keywords = HashMap::new();
-
-        keywords.insert(String::from("and"),    TokenType::AND);
-        keywords.insert(String::from("class"),  TokenType::CLASS);
-        keywords.insert(String::from("else"),   TokenType::ELSE);
-        keywords.insert(String::from("false"),  TokenType::FALSE);
-        keywords.insert(String::from("for"),    TokenType::FOR);
-        keywords.insert(String::from("fun"),    TokenType::FUN);
-        keywords.insert(String::from("if"),     TokenType::IF);
-        keywords.insert(String::from("nil"),    TokenType::NIL);
-        keywords.insert(String::from("or"),     TokenType::OR);
-        keywords.insert(String::from("print"),  TokenType::PRINT);
-        keywords.insert(String::from("return"), TokenType::RETURN);
-        keywords.insert(String::from("super"),  TokenType::SUPER);
-        keywords.insert(String::from("this"),   TokenType::THIS);
-        keywords.insert(String::from("true"),   TokenType::TRUE);
-        keywords.insert(String::from("var"),    TokenType::VAR);
-        keywords.insert(String::from("while"),  TokenType::WHILE);
-
-        keywords
+    pub fn set_source(&mut self, source: String) {
+        self.source_len = source.chars().count();
+        self.source = source;
+        self.tokens.clear();
+        self.streamed = 0;
+        self.emitted_eof = false;
+        self.start = 0;
+        self.start_position = Position { line: 1, column: 1, offset: 0 };
+        self.current = 0;
+        self.line = 1;
+        self.column = 1;
+        self.offset = 0;
+        self.last_token_end = 0;
+    }
+
+    pub fn with_tab_width(mut self, tab_width: usize) -> Scanner {
+        self.options.tab_width = tab_width;
+        self
     }
 }
 
 
 impl Scanner {
+    // picks up wherever next_token left off, so the result always holds the
+    // whole source, streamed tokens included
     pub fn scan_tokens(&mut self) -> &Vec<Token> {
+        while self.scan_next() {}
+
+        &self.tokens
+    }
+
+    pub fn next_token(&mut self) -> Option<Token> {
+        let token = self.peek_token()?.clone();
+        self.streamed += 1;
+
+        Some(token)
+    }
+
+    pub fn peek_token(&mut self) -> Option<&Token> {
+        if self.streamed == self.tokens.len() && !self.scan_next() {
+            return None;
+        }
+
+        self.tokens.get(self.streamed)
+    }
+
+    pub fn find_token_at(&self, line: usize, column: usize) -> Option<&Token> {
+        self.tokens.iter().find(|token| token.span.contains(line, column))
+    }
+
+    pub fn as_source_view(&self) -> SourceView<'_> {
+        SourceView::new(&self.source).with_tab_width(self.options.tab_width)
+    }
+
+    pub fn source_byte_range(&self, token: &Token) -> Option<&str> {
+        self.source.get(token.span.start.offset..token.span.end.offset)
+    }
+
+    pub fn annotate_source(&self, span: Span) -> String {
+        let mut annotated = Vec::new();
+
+        // lines and columns are 1-based, so treat 0 as the first one
+        let start_line = span.start.line.max(1);
+        let end_line = span.end.line.max(start_line);
+
+        for line in start_line..=end_line {
+            let text = self.source.split('\n').nth(line - 1).unwrap_or("");
+            let text = text.strip_suffix('\r').unwrap_or(text);
+            // columns on the first line start after the BOM
+            let text = if line == 1 { text.strip_prefix('\u{FEFF}').unwrap_or(text) } else { text };
+
+            let from = if line == start_line { span.start.column.max(1) } else { 1 };
+            let to = if line == end_line { span.end.column } else { usize::MAX };
+
+            // columns count a tab as tab_width, so walk the line the way the
+            // scanner does; the padding keeps the line's own tabs so the
+            // carets line up however the terminal renders them
+            let mut padding = String::new();
+            let mut carets = 0;
+            let mut column = 1;
+
+            for c in text.chars() {
+                if column < from {
+                    padding.push(if c == '\t' { '\t' } else { ' ' });
+                }
+                else if column < to {
+                    carets += 1;
+                }
+
+                column += if c == '\t' { self.options.tab_width } else { 1 };
+            }
+
+            // a span past the end of the line, like EOF's, points after it
+            if column < from {
+                padding.push_str(&" ".repeat(from - column));
+            }
+
+            annotated.push(text.to_string());
+            annotated.push(format!("{}{}", padding, "^".repeat(carets.max(1))));
+        }
+
+        annotated.join("\n")
+    }
+
+    pub fn token_at_offset(&self, byte_offset: usize) -> Option<&Token> {
+        // tokens are scanned in source order, so their start offsets are sorted
+        let after = self.tokens.partition_point(|token| token.span.start.offset <= byte_offset);
+        let token = self.tokens.get(after.checked_sub(1)?)?;
+
+        if byte_offset < token.span.end.offset { Some(token) } else { None }
+    }
+
+    // lines are 1-based, so there is no line 0 to ask about
+    pub fn tokens_on_line(&self, line: usize) -> Option<Vec<&Token>> {
+        if line == 0 {
+            return None;
+        }
+
+        Some(self.tokens.iter()
+            .filter(|token| token.span.start.line == line && !token.is_eof())
+            .collect())
+    }
+
+    // takes the literal with its quotes; there are no escape sequences, so
+    // the value is everything between them
+    pub fn scan_string_literal(input: &str) -> Result<String, ScanError> {
+        let body = input.strip_prefix('"').ok_or_else(|| ScanError::InvalidLiteral(input.to_string()))?;
+        let body = body.strip_suffix('"').ok_or(ScanError::UnterminatedString)?;
+
+        if body.contains('"') {
+            return Err(ScanError::InvalidLiteral(input.to_string()));
+        }
+
+        Ok(body.to_string())
+    }
+
+    // only the forms scan_token recognises: digits with an optional
+    // fractional part
+    pub fn scan_number_literal(input: &str) -> Result<f64, ScanError> {
+        let (whole, fraction) = match input.split_once('.') {
+            Some((whole, fraction)) => (whole, Some(fraction)),
+            None => (input, None)
+        };
+
+        let is_digits = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+
+        if !is_digits(whole) || fraction.is_some_and(|fraction| !is_digits(fraction)) {
+            return Err(ScanError::InvalidLiteral(input.to_string()));
+        }
+
+        input.parse::<f64>().map_err(|_| ScanError::InvalidLiteral(input.to_string()))
+    }
+
+    // scans until one more token is pushed; false once EOF has been emitted
+    fn scan_next(&mut self) -> bool {
+        let scanned = self.tokens.len();
+        self.skip_bom();
+        self.skip_shebang();
+
         while !self.is_end() {
-            self.start = self.current;
+            self.begin_token();
             self.scan_token();
+
+            if self.tokens.len() > scanned {
+                return true;
+            }
         }
 
-        self.add_token(TokenType::EOF);
+        if self.emitted_eof {
+            return false;
+        }
 
-        &self.tokens
+        self.emitted_eof = true;
+        self.begin_token();
+        self.add_token(TokenType::EOF);
+        true
     }
 
 
     fn scan_token(&mut self){
         let cur_token = self.advance();
 
+        if let Some(token_type) = SINGLE_CHAR_TOKENS.get(&cur_token) {
+            self.add_token(token_type.clone());
+            return;
+        }
+
         match cur_token {
-            '(' => self.add_token(TokenType::LEFTPAREN),
-            ')' => self.add_token(TokenType::RIGHTPAREN),
-            '{' => self.add_token(TokenType::LEFTBRACE),
-            '}' => self.add_token(TokenType::LEFTBRACE),
-            ',' => self.add_token(TokenType::COMMA),
-            '.' => self.add_token(TokenType::DOT),
-            '+' => self.add_token(TokenType::PLUS),
-            '-' => self.add_token(TokenType::MINUS),
-            '*' => self.add_token(TokenType::STAR),
-            '/' => self.add_token(TokenType::SLASH),
-            ';' => self.add_token(TokenType::SEMICOLON),
+            '.' => {
+                        let token = if self.peek_ahead(1) == Some('.') && self.peek_ahead(2) == Some('.') {
+                            self.advance();
+                            self.advance();
+                            TokenType::ELLIPSIS
+                        } else {TokenType::DOT};
+                        self.add_token(token);
+                    },
             '!' => {
-                        let token = if self.match_token('='){TokenType::BANGEQUAL} else {TokenType::EQUAL};
+                        let token = if self.match_token('='){TokenType::BANGEQUAL} else {TokenType::BANG};
                         self.add_token(token);
                     },
             '=' => {
@@ -153,20 +540,62 @@ impl Scanner {
     }
 
 
+    fn skip_bom(&mut self) {
+        if self.current != 0 || self.peek() != '\u{FEFF}' {
+            return;
+        }
+
+        // the offset keeps counting the BOM's bytes, but columns and the
+        // first token's preceding whitespace start after it
+        self.advance();
+        self.column = 1;
+        self.last_token_end = self.offset;
+    }
+
+    fn skip_shebang(&mut self) {
+        if self.line != 1 || self.column != 1 || self.peek() != '#' || self.peek_next() != '!' {
+            return;
+        }
+
+        // the whole first line, newline included, is skipped; the script
+        // itself starts on line 2 and its first token has no preceding
+        // whitespace, as after a BOM
+        while self.peek() != '\n' && !self.is_end() {
+            self.advance();
+        }
+
+        if !self.is_end() {
+            self.advance();
+            self.line += 1;
+        }
+
+        self.last_token_end = self.offset;
+    }
+
     fn match_token(&mut self, expected_token: char) -> bool {
         if self.is_end() || self.peek() != expected_token { //either we reach end or we did not
             //found what we wanted
-            return false;
+            false
         }
         else{
-            self.current += 1;
-            return true;
+            self.advance();
+            true
         }
     }
 
+    #[track_caller]
     fn advance(&mut self) -> char {
-        let token = self.source.chars().nth(self.current).unwrap();
-        (*self).current += 1;
+        let token = self.source.chars().nth(self.current)
+            .expect("Scanner advance: current index out of bounds — this is a bug");
+        self.current += 1;
+        self.offset += token.len_utf8();
+
+        match token {
+            '\n' => self.column = 1,
+            '\t' => self.column += self.options.tab_width,
+            _ => self.column += 1
+        }
+
         token
     }
 
@@ -183,8 +612,9 @@ impl Scanner {
 
         self.advance();
         
-        let value: String = self.substr();
-        self.add_token_verbose(TokenType::STRING, Some(Box::new(value)));
+        let value = Scanner::scan_string_literal(&self.substr())
+            .expect("Scanner string: lexeme is not a valid string literal — this is a bug");
+        self.add_token_verbose(TokenType::STRING, Some(LiteralValue::Str(value)));
     }
 
     fn number(&mut self) {
@@ -195,8 +625,9 @@ impl Scanner {
 
             while self.is_digit(self.peek()) { self.advance(); }
         }
-        let val = self.substr().parse::<usize>();
-        self.add_token_verbose(TokenType::NUMBER, Some(Box::new(val)));
+        let val = Scanner::scan_number_literal(&self.substr())
+            .expect("Scanner number: lexeme is not a valid number literal — this is a bug");
+        self.add_token_verbose(TokenType::NUMBER, Some(LiteralValue::Number(val)));
     }
 
     fn identifier(&mut self) {
@@ -216,27 +647,34 @@ impl Scanner {
 }
 
 impl Scanner {
+    fn begin_token(&mut self) {
+        self.start = self.current;
+        self.start_position = self.position();
+    }
+
+    fn position(&self) -> Position {
+        Position { line: self.line, column: self.column, offset: self.offset }
+    }
+
     fn is_end(&self) -> bool {
-        if self.current >= self.source.len() {
-            true
-        }
-        else {
-            false
-        }
+        self.current >= self.source_len
     }
 
-    fn add_token(&mut self, token: TokenType) -> () {
+    fn add_token(&mut self, token: TokenType) {
         self.add_token_verbose(token, None);
     }
 
-    fn add_token_verbose(&mut self, token_type: TokenType, literal: Option<Box<dyn Any>>){
+    fn add_token_verbose(&mut self, token_type: TokenType, literal: Option<LiteralValue>){
         let token = Token {
-            line: self.line,
+            line: self.start_position.line,
+            span: Span { start: self.start_position, end: self.position() },
+            whitespace_start: self.last_token_end,
             token_type,
             lexeme: self.substr(),
-            literal: if let Some(lit) = literal { lit } else { Box::new(TokenType::NIL) }
+            literal: literal.unwrap_or(LiteralValue::Nil)
         };
 
+        self.last_token_end = self.offset;
         self.tokens.push(token);
     }
 
@@ -244,28 +682,684 @@ impl Scanner {
         self.source.substr(self.start, self.current)
     }
 
+    // 0 is the character advance() last returned, 1 is the next one
+    fn peek_ahead(&self, n: usize) -> Option<char> {
+        let index = (self.current + n).checked_sub(1)?;
+        self.source.chars().nth(index)
+    }
+
     fn peek(&self) -> char {
-        if self.current >= self.source.len() {return '\0';}
-        self.source.chars().nth(self.current).unwrap()
+        self.peek_ahead(1).unwrap_or('\0')
     }
 
     fn peek_next(&self) -> char {
-        if self.current + 1 >= self.source.len() {return '\0';}
-
-        self.source.chars().nth(self.current + 1).unwrap()
+        self.peek_ahead(2).unwrap_or('\0')
     }
 
     fn is_alphanumeric(&self, token: char) -> bool {
-        return self.is_digit(token) || self.is_alpha(token);
+        self.is_digit(token) || self.is_alpha(token)
     }
 
     fn is_digit(&self, token: char) -> bool {
-        return token >= '0' && token <= '9';
+        token.is_ascii_digit()
     }
 
     fn is_alpha(&self, token: char) -> bool {
-        return (token >= 'a' && token <= 'z') ||
-            (token >= 'A' && token <= 'Z') ||
-            token == '_';
+        token.is_ascii_alphabetic() || token == '_'
+    }
+}
+
+// Scanning errors are still reported by panicking; this turns them into a
+// ScanError until the scanner returns Results itself. The panic hook is left
+// alone, so it still runs for the panic; a caller that reports the ScanError
+// itself can install a quiet one.
+pub struct PanicFreeScanner;
+
+impl PanicFreeScanner {
+    pub fn scan(source: String) -> Result<Vec<Token>, ScanError> {
+        let result = panic::catch_unwind(|| {
+            let mut scanner = Scanner::new(source);
+            scanner.scan_tokens();
+            scanner.tokens
+        });
+
+        result.map_err(|payload| {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            }
+            else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            }
+            else {
+                String::from("unknown panic")
+            };
+
+            ScanError::InternalError(message)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token_types(source: &str) -> Vec<TokenType> {
+        let mut scanner = Scanner::new(source.to_string());
+        scanner.scan_tokens().iter().map(|token| token.token_type.clone()).collect()
+    }
+
+    #[test]
+    #[should_panic(expected = "Scanner advance: current index out of bounds")]
+    fn advance_past_end_panics_with_context() {
+        let mut scanner = Scanner::new(String::from("a"));
+        scanner.current = 5;
+        scanner.advance();
+    }
+
+    #[test]
+    fn is_eof_only_for_eof_token() {
+        let mut scanner = Scanner::new(String::from("var x;"));
+        let eofs: Vec<bool> = scanner.scan_tokens().iter().map(Token::is_eof).collect();
+
+        assert_eq!(eofs, vec![false, false, false, true]);
+    }
+
+    #[test]
+    fn matches_compares_token_type() {
+        let mut scanner = Scanner::new(String::from("( >= x"));
+        let tokens = scanner.scan_tokens();
+
+        assert!(tokens[0].matches(TokenType::LEFTPAREN));
+        assert!(tokens[1].matches(TokenType::GREATEREQUAL));
+        assert!(!tokens[1].matches(TokenType::GREATER));
+        assert!(tokens[2].matches(TokenType::IDENTIFIER));
+        assert!(tokens[3].matches(TokenType::EOF));
+        assert!(!tokens[3].matches(TokenType::IDENTIFIER));
+    }
+
+    #[test]
+    fn peek_token_does_not_consume() {
+        let mut scanner = Scanner::new(String::from("a b"));
+
+        assert_eq!(scanner.peek_token().map(|token| token.lexeme.clone()), Some(String::from("a")));
+        assert_eq!(scanner.peek_token().map(|token| token.lexeme.clone()), Some(String::from("a")));
+        assert_eq!(scanner.next_token().map(|token| token.lexeme), Some(String::from("a")));
+        assert_eq!(scanner.next_token().map(|token| token.lexeme), Some(String::from("b")));
+        assert!(scanner.next_token().is_some_and(|token| token.is_eof()));
+        assert!(scanner.next_token().is_none());
+        assert!(scanner.peek_token().is_none());
+    }
+
+    #[test]
+    fn scan_tokens_keeps_streamed_tokens() {
+        let mut scanner = Scanner::new(String::from("a b c"));
+        scanner.peek_token();
+        scanner.next_token();
+
+        let lexemes: Vec<&str> = scanner.scan_tokens().iter()
+            .filter(|token| !token.is_eof())
+            .map(|token| token.lexeme.as_str())
+            .collect();
+        assert_eq!(lexemes, vec!["a", "b", "c"]);
+        assert!(scanner.find_token_at(1, 1).is_some());
+    }
+
+    #[test]
+    fn next_token_after_scan_tokens_emits_one_eof() {
+        let mut scanner = Scanner::new(String::from("a"));
+        scanner.scan_tokens();
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.tokens.iter().filter(|token| token.is_eof()).count(), 1);
+        assert_eq!(scanner.next_token().map(|token| token.lexeme), Some(String::from("a")));
+        assert!(scanner.next_token().is_some_and(|token| token.is_eof()));
+        assert!(scanner.next_token().is_none());
+    }
+
+    #[test]
+    fn shebang_line_is_skipped() {
+        assert_eq!(token_types("#!/usr/bin/env cookielang\nprint \"hello\";"), token_types("print \"hello\";"));
+        assert_eq!(token_types("#!/usr/bin/env cookielang"), vec![TokenType::EOF]);
+    }
+
+    #[test]
+    fn shebang_positions_agree_with_source() {
+        let source = "#!/x\nfoo";
+        let mut scanner = Scanner::new(source.to_string());
+        let token = scanner.scan_tokens()[0].clone();
+
+        assert_eq!(token.span().start, Position { line: 2, column: 1, offset: 5 });
+        assert_eq!(scanner.as_source_view().line_col(5), (2, 1));
+        assert_eq!(token.preceding_whitespace(source), "");
+        assert_eq!(scanner.annotate_source(token.span()), "foo\n^^^");
+    }
+
+    #[test]
+    fn hash_bang_after_first_line_is_not_skipped() {
+        let result = panic::catch_unwind(|| token_types("x\n#!/x"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn find_token_at_middle_of_identifier() {
+        let mut scanner = Scanner::new(String::from("var counter = 1;"));
+        scanner.scan_tokens();
+
+        assert_eq!(scanner.find_token_at(1, 7).map(|token| token.lexeme.as_str()), Some("counter"));
+    }
+
+    #[test]
+    fn find_token_at_operator() {
+        let mut scanner = Scanner::new(String::from("a\nb >= c"));
+        scanner.scan_tokens();
+
+        assert!(scanner.find_token_at(2, 3).is_some_and(|token| token.matches(TokenType::GREATEREQUAL)));
+        assert!(scanner.find_token_at(2, 4).is_some_and(|token| token.matches(TokenType::GREATEREQUAL)));
+    }
+
+    #[test]
+    fn find_token_at_whitespace_is_none() {
+        let mut scanner = Scanner::new(String::from("a  b"));
+        scanner.scan_tokens();
+
+        assert!(scanner.find_token_at(1, 2).is_none());
+        assert!(scanner.find_token_at(1, 3).is_none());
+    }
+
+    #[test]
+    fn find_token_at_past_end_of_line_is_none() {
+        let mut scanner = Scanner::new(String::from("ab;\ncd"));
+        scanner.scan_tokens();
+
+        assert!(scanner.find_token_at(1, 4).is_none());
+        assert!(scanner.find_token_at(1, 40).is_none());
+        assert!(scanner.find_token_at(2, 3).is_none());
+    }
+
+    fn lexemes_on_line(scanner: &Scanner, line: usize) -> Option<Vec<&str>> {
+        scanner.tokens_on_line(line)
+            .map(|tokens| tokens.iter().map(|token| token.lexeme.as_str()).collect())
+    }
+
+    #[test]
+    fn tokens_on_line_with_multiple_tokens() {
+        let mut scanner = Scanner::new(String::from("var a = 1;\nprint a;\n"));
+        scanner.scan_tokens();
+
+        assert_eq!(lexemes_on_line(&scanner, 1), Some(vec!["var", "a", "=", "1", ";"]));
+        assert_eq!(lexemes_on_line(&scanner, 2), Some(vec!["print", "a", ";"]));
+    }
+
+    #[test]
+    fn tokens_on_last_line_without_newline() {
+        let mut scanner = Scanner::new(String::from("a;\nb c"));
+        scanner.scan_tokens();
+
+        assert_eq!(lexemes_on_line(&scanner, 2), Some(vec!["b", "c"]));
+    }
+
+    #[test]
+    fn tokens_on_empty_line() {
+        let mut scanner = Scanner::new(String::from("a;\n\nb;"));
+        scanner.scan_tokens();
+
+        assert_eq!(lexemes_on_line(&scanner, 2), Some(vec![]));
+        assert_eq!(lexemes_on_line(&scanner, 3), Some(vec!["b", ";"]));
+    }
+
+    #[test]
+    fn tokens_on_line_zero_is_none() {
+        let mut scanner = Scanner::new(String::from("a;"));
+        scanner.scan_tokens();
+
+        assert!(scanner.tokens_on_line(0).is_none());
+    }
+
+    #[test]
+    fn literal_display_for_error() {
+        assert_eq!(LiteralValue::Nil.display_for_error(), "nil");
+        assert_eq!(LiteralValue::Bool(true).display_for_error(), "true");
+        assert_eq!(LiteralValue::Bool(false).display_for_error(), "false");
+        assert_eq!(LiteralValue::Number(3.5).display_for_error(), "3.5");
+        assert_eq!(LiteralValue::Number(2.0).display_for_error(), "2");
+        assert_eq!(LiteralValue::Str(String::from("hi")).display_for_error(), "\"hi\"");
+    }
+
+    #[test]
+    fn literal_display_for_error_truncates_long_strings() {
+        let exact = "a".repeat(LiteralValue::ERROR_STR_LIMIT);
+        let long = "é".repeat(LiteralValue::ERROR_STR_LIMIT + 5);
+
+        assert_eq!(LiteralValue::Str(exact.clone()).display_for_error(), format!("\"{}\"", exact));
+        assert_eq!(LiteralValue::Str(long).display_for_error(), format!("\"{}...\"", "é".repeat(LiteralValue::ERROR_STR_LIMIT)));
+    }
+
+    #[test]
+    fn single_char_tokens() {
+        assert_eq!(token_types("(){},.-+;/*"), vec![
+            TokenType::LEFTPAREN, TokenType::RIGHTPAREN, TokenType::LEFTBRACE, TokenType::RIGHTBRACE,
+            TokenType::COMMA, TokenType::DOT, TokenType::MINUS, TokenType::PLUS,
+            TokenType::SEMICOLON, TokenType::SLASH, TokenType::STAR, TokenType::EOF
+        ]);
+    }
+
+    #[test]
+    fn phf_keywords_match_keyword_slice() {
+        assert_eq!(PHF_KEYWORDS.len(), KEYWORDS.len());
+
+        for (name, token_type) in KEYWORDS {
+            assert_eq!(PHF_KEYWORDS.get(name), Some(token_type), "{}", name);
+        }
+    }
+
+    #[test]
+    fn phf_keywords_scan_like_default_keywords() {
+        let source = "class A { fun f() { return nil or this; } } var classy = true;";
+        let mut phf_scanner = Scanner::with_phf_keywords(source.to_string());
+
+        let phf_types: Vec<TokenType> = phf_scanner.scan_tokens().iter()
+            .map(|token| token.token_type.clone())
+            .collect();
+        assert_eq!(phf_types, token_types(source));
+    }
+
+    #[test]
+    fn panic_free_scanner_returns_tokens() {
+        let tokens = PanicFreeScanner::scan(String::from("print 1;")).unwrap();
+
+        assert_eq!(tokens.len(), 4);
+        assert!(tokens[3].is_eof());
+    }
+
+    #[test]
+    fn panic_free_scanner_catches_unterminated_string() {
+        let result = PanicFreeScanner::scan(String::from("print \"oops;"));
+
+        assert!(matches!(result, Err(ScanError::InternalError(message)) if message == "Undetermined string"));
+    }
+
+    #[test]
+    fn panic_free_scanner_catches_invalid_token() {
+        let result = PanicFreeScanner::scan(String::from("var a = 1 @ 2;"));
+
+        assert!(matches!(result, Err(ScanError::InternalError(message)) if message == "Invalid token"));
+    }
+
+    fn annotate_token(scanner: &mut Scanner, index: usize) -> String {
+        let span = scanner.scan_tokens()[index].span();
+        scanner.annotate_source(span)
+    }
+
+    #[test]
+    fn annotate_single_char_token() {
+        let mut scanner = Scanner::new(String::from("a = b;"));
+
+        assert_eq!(annotate_token(&mut scanner, 1), "a = b;\n  ^");
+    }
+
+    #[test]
+    fn annotate_multi_char_token() {
+        let mut scanner = Scanner::new(String::from("x\nvar count = 1;"));
+
+        assert_eq!(annotate_token(&mut scanner, 2), "var count = 1;\n    ^^^^^");
+    }
+
+    #[test]
+    fn annotate_multi_line_span() {
+        let mut scanner = Scanner::new(String::from("x = \"ab\ncd\";"));
+
+        assert_eq!(annotate_token(&mut scanner, 2), "x = \"ab\n    ^^^\ncd\";\n^^^");
+    }
+
+    #[test]
+    fn annotate_keeps_tabs_with_wide_tab_width() {
+        let mut scanner = Scanner::new(String::from("\tif\t(x)")).with_tab_width(4);
+        let tokens = scanner.scan_tokens().clone();
+
+        assert_eq!(tokens[1].span().start.column, 11);
+        assert_eq!(scanner.annotate_source(tokens[0].span()), "\tif\t(x)\n\t^^");
+        assert_eq!(scanner.annotate_source(tokens[1].span()), "\tif\t(x)\n\t  \t^");
+    }
+
+    #[test]
+    fn annotate_strips_carriage_return_and_bom() {
+        let mut scanner = Scanner::new(String::from("\u{FEFF}ab;\r\ncd"));
+
+        assert_eq!(annotate_token(&mut scanner, 1), "ab;\n  ^");
+        assert_eq!(annotate_token(&mut scanner, 2), "cd\n^^");
+    }
+
+    #[test]
+    fn annotate_clamps_line_and_column_zero() {
+        let scanner = Scanner::new(String::from("abc"));
+        let zero = Position { line: 0, column: 0, offset: 0 };
+
+        assert_eq!(scanner.annotate_source(Span { start: zero, end: zero }), "abc\n^");
+        assert_eq!(scanner.annotate_source(Span { start: zero, end: Position { line: 1, column: 3, offset: 2 } }), "abc\n^^");
+    }
+
+    #[test]
+    fn source_byte_range_slices_token_text() {
+        let mut scanner = Scanner::new(String::from("\"π\" + \"héllo\";"));
+        let tokens = scanner.scan_tokens().clone();
+
+        assert_eq!(scanner.source_byte_range(&tokens[0]), Some("\"π\""));
+        assert_eq!(scanner.source_byte_range(&tokens[2]), Some("\"héllo\""));
+
+        let mut synthetic = tokens[0].clone();
+        synthetic.span.end.offset = 100;
+        assert_eq!(scanner.source_byte_range(&synthetic), None);
+    }
+
+    fn preceding_whitespace(source: &str) -> Vec<String> {
+        let mut scanner = Scanner::new(source.to_string());
+        scanner.scan_tokens().iter()
+            .map(|token| token.preceding_whitespace(source).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn preceding_whitespace_between_tokens() {
+        assert_eq!(preceding_whitespace("a  b\t c"), vec!["", "  ", "\t ", ""]);
+    }
+
+    #[test]
+    fn preceding_whitespace_across_newlines() {
+        assert_eq!(preceding_whitespace("a;\n\n  b \n"), vec!["", "", "\n\n  ", " \n"]);
+    }
+
+    #[test]
+    fn preceding_whitespace_of_adjacent_tokens_is_empty() {
+        assert_eq!(preceding_whitespace("f(x);"), vec![""; 6]);
+    }
+
+    fn lexeme_at_offset(scanner: &Scanner, byte_offset: usize) -> Option<&str> {
+        scanner.token_at_offset(byte_offset).map(|token| token.lexeme.as_str())
+    }
+
+    #[test]
+    fn token_at_offset_within_tokens() {
+        let mut scanner = Scanner::new(String::from("var total = 10;"));
+        scanner.scan_tokens();
+
+        assert_eq!(lexeme_at_offset(&scanner, 4), Some("total"));
+        assert_eq!(lexeme_at_offset(&scanner, 6), Some("total"));
+        assert_eq!(lexeme_at_offset(&scanner, 8), Some("total"));
+        assert_eq!(lexeme_at_offset(&scanner, 14), Some(";"));
+    }
+
+    #[test]
+    fn token_at_offset_end_is_exclusive() {
+        let mut scanner = Scanner::new(String::from("ab;"));
+        scanner.scan_tokens();
+
+        assert_eq!(lexeme_at_offset(&scanner, 1), Some("ab"));
+        assert_eq!(lexeme_at_offset(&scanner, 2), Some(";"));
+    }
+
+    #[test]
+    fn token_at_offset_in_whitespace_is_none() {
+        let mut scanner = Scanner::new(String::from("a  \n b"));
+        scanner.scan_tokens();
+
+        assert_eq!(lexeme_at_offset(&scanner, 1), None);
+        assert_eq!(lexeme_at_offset(&scanner, 3), None);
+        assert_eq!(lexeme_at_offset(&scanner, 5), Some("b"));
+    }
+
+    #[test]
+    fn token_at_offset_past_end_of_file_is_none() {
+        let mut scanner = Scanner::new(String::from("a b"));
+        scanner.scan_tokens();
+
+        assert_eq!(lexeme_at_offset(&scanner, 3), None);
+        assert_eq!(lexeme_at_offset(&scanner, 100), None);
+    }
+
+    #[test]
+    fn scope_is_a_keyword() {
+        assert_eq!(token_types("scope { var x; }"), vec![
+            TokenType::SCOPE, TokenType::LEFTBRACE, TokenType::VAR, TokenType::IDENTIFIER,
+            TokenType::SEMICOLON, TokenType::RIGHTBRACE, TokenType::EOF
+        ]);
+        assert_eq!(token_types("scoped"), vec![TokenType::IDENTIFIER, TokenType::EOF]);
+    }
+
+    #[test]
+    fn tab_advances_column_by_tab_width() {
+        let mut scanner = Scanner::with_options(String::from("\tx\n\t\ty"), ScannerOptions { tab_width: 4 });
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens[0].span().start.column, 5);
+        assert_eq!(tokens[1].span().start.column, 9);
+    }
+
+    #[test]
+    fn default_tab_width_counts_a_tab_as_one_column() {
+        let mut scanner = Scanner::new(String::from("\tx"));
+
+        assert_eq!(scanner.scan_tokens()[0].span().start.column, 2);
+
+        let mut scanner = Scanner::new(String::from("\tx")).with_tab_width(8);
+        assert_eq!(scanner.scan_tokens()[0].span().start.column, 9);
+    }
+
+    #[test]
+    fn tokens_on_same_line() {
+        let mut scanner = Scanner::new(String::from("a b\nc"));
+        let tokens = scanner.scan_tokens();
+
+        assert!(tokens[0].is_on_same_line_as(&tokens[1]));
+        assert!(!tokens[1].is_on_same_line_as(&tokens[2]));
+        assert_eq!(tokens[0].lines_between(&tokens[1]), 0);
+    }
+
+    #[test]
+    fn lines_between_adjacent_and_blank_lines() {
+        let mut scanner = Scanner::new(String::from("a\nb\n\n\n\nc"));
+        let tokens = scanner.scan_tokens();
+
+        assert_eq!(tokens[0].lines_between(&tokens[1]), 0);
+        assert_eq!(tokens[1].lines_between(&tokens[2]), 3);
+        assert_eq!(tokens[2].lines_between(&tokens[1]), 3);
+    }
+
+    #[test]
+    fn lines_between_starts_after_a_multi_line_token() {
+        let mut scanner = Scanner::new(String::from("\"a\nb\"\n\nc"));
+        let tokens = scanner.scan_tokens();
+
+        assert!(!tokens[0].is_on_same_line_as(&tokens[1]));
+        assert_eq!(tokens[0].lines_between(&tokens[1]), 1);
+    }
+
+    #[test]
+    fn set_source_rescans_from_the_start() {
+        let mut scanner = Scanner::new(String::from("a\nb c"));
+        scanner.scan_tokens();
+        scanner.set_source(String::from("x;"));
+
+        let tokens = scanner.scan_tokens().clone();
+        let lexemes: Vec<&str> = tokens.iter().map(|token| token.lexeme.as_str()).collect();
+
+        assert_eq!(lexemes, vec!["x", ";", ""]);
+        assert_eq!(tokens[0].span().start, Position { line: 1, column: 1, offset: 0 });
+        assert_eq!(tokens[0].preceding_whitespace("x;"), "");
+    }
+
+    #[test]
+    fn set_source_resets_streaming() {
+        let mut scanner = Scanner::new(String::from("a"));
+        while scanner.next_token().is_some() {}
+        scanner.set_source(String::from("b"));
+
+        assert_eq!(scanner.next_token().map(|token| token.lexeme), Some(String::from("b")));
+        assert!(scanner.next_token().is_some_and(|token| token.is_eof()));
+        assert!(scanner.next_token().is_none());
+    }
+
+    #[test]
+    fn set_source_keeps_keywords_and_options() {
+        let mut scanner = Scanner::with_keywords_from_slice(String::from("x"), &[("let", TokenType::VAR)])
+            .with_tab_width(4);
+        let Keywords::Map(map) = &scanner.keywords else { panic!("expected a HashMap keyword table") };
+        let capacity = map.capacity();
+
+        scanner.scan_tokens();
+        scanner.set_source(String::from("\tlet"));
+        let token = scanner.scan_tokens()[0].clone();
+
+        assert!(token.matches(TokenType::VAR));
+        assert_eq!(token.span().start.column, 5);
+        assert!(matches!(&scanner.keywords, Keywords::Map(map) if map.capacity() == capacity));
+    }
+
+    #[test]
+    fn scan_string_literal_takes_text_between_quotes() {
+        assert_eq!(Scanner::scan_string_literal("\"hello\""), Ok(String::from("hello")));
+        assert_eq!(Scanner::scan_string_literal("\"\""), Ok(String::new()));
+        assert_eq!(Scanner::scan_string_literal("\"a\nb\""), Ok(String::from("a\nb")));
+    }
+
+    #[test]
+    fn scan_string_literal_has_no_escape_sequences() {
+        assert_eq!(Scanner::scan_string_literal("\"a\\nb\""), Ok(String::from("a\\nb")));
+        assert_eq!(Scanner::scan_string_literal("\"\\\""), Ok(String::from("\\")));
+    }
+
+    #[test]
+    fn scan_string_literal_errors() {
+        assert_eq!(Scanner::scan_string_literal("hello\""), Err(ScanError::InvalidLiteral(String::from("hello\""))));
+        assert_eq!(Scanner::scan_string_literal("\"hello"), Err(ScanError::UnterminatedString));
+        assert_eq!(Scanner::scan_string_literal("\""), Err(ScanError::UnterminatedString));
+        assert_eq!(Scanner::scan_string_literal("\"a\"b\""), Err(ScanError::InvalidLiteral(String::from("\"a\"b\""))));
+    }
+
+    #[test]
+    fn scan_number_literal_formats() {
+        assert_eq!(Scanner::scan_number_literal("0"), Ok(0.0));
+        assert_eq!(Scanner::scan_number_literal("42"), Ok(42.0));
+        assert_eq!(Scanner::scan_number_literal("007"), Ok(7.0));
+        assert_eq!(Scanner::scan_number_literal("3.25"), Ok(3.25));
+    }
+
+    #[test]
+    fn scan_number_literal_edge_cases() {
+        assert_eq!(Scanner::scan_number_literal(&"9".repeat(400)), Ok(f64::INFINITY));
+        assert_eq!(Scanner::scan_number_literal(&format!("{:.1}", f64::MAX)), Ok(f64::MAX));
+
+        let smallest_normal = format!("0.{}22250738585072014", "0".repeat(307));
+        assert_eq!(Scanner::scan_number_literal(&smallest_normal), Ok(f64::MIN_POSITIVE));
+
+        let subnormal = format!("0.{}5", "0".repeat(323));
+        let value = Scanner::scan_number_literal(&subnormal).unwrap();
+        assert!(value > 0.0 && !value.is_normal());
+    }
+
+    #[test]
+    fn scan_number_literal_errors() {
+        for input in ["", ".5", "5.", "1.2.3", "-1", "1e5", "0x10", "12a", " 1"] {
+            assert_eq!(Scanner::scan_number_literal(input), Err(ScanError::InvalidLiteral(input.to_string())), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn peek_ahead_matches_peek_and_peek_next() {
+        let mut scanner = Scanner::new(String::from("aé"));
+
+        assert_eq!(scanner.peek_ahead(0), None);
+        assert_eq!(scanner.peek_ahead(1), Some(scanner.peek()));
+        assert_eq!(scanner.peek_ahead(2), Some(scanner.peek_next()));
+
+        scanner.advance();
+        assert_eq!(scanner.peek_ahead(0), Some('a'));
+        assert_eq!(scanner.peek_ahead(1), Some('é'));
+    }
+
+    #[test]
+    fn peek_ahead_past_end_of_input() {
+        let mut scanner = Scanner::new(String::from("ab"));
+        scanner.advance();
+        scanner.advance();
+
+        assert_eq!(scanner.peek_ahead(0), Some('b'));
+        assert_eq!(scanner.peek_ahead(1), None);
+        assert_eq!(scanner.peek_ahead(5), None);
+        assert_eq!(scanner.peek(), '\0');
+        assert_eq!(scanner.peek_next(), '\0');
+    }
+
+    #[test]
+    fn ellipsis_and_dots() {
+        assert_eq!(token_types("..."), vec![TokenType::ELLIPSIS, TokenType::EOF]);
+        assert_eq!(token_types(".."), vec![TokenType::DOT, TokenType::DOT, TokenType::EOF]);
+        assert_eq!(token_types("."), vec![TokenType::DOT, TokenType::EOF]);
+        assert_eq!(token_types("...."), vec![TokenType::ELLIPSIS, TokenType::DOT, TokenType::EOF]);
+    }
+
+    #[test]
+    fn ellipsis_between_operands() {
+        assert_eq!(token_types("a...b"), vec![TokenType::IDENTIFIER, TokenType::ELLIPSIS, TokenType::IDENTIFIER, TokenType::EOF]);
+        assert_eq!(token_types("0...5"), vec![TokenType::NUMBER, TokenType::ELLIPSIS, TokenType::NUMBER, TokenType::EOF]);
+
+        let mut scanner = Scanner::new(String::from("f(...args)"));
+        let ellipsis = scanner.scan_tokens()[2].clone();
+        assert_eq!(ellipsis.lexeme, "...");
+        assert_eq!(ellipsis.span().start.column, 3);
+        assert_eq!(ellipsis.span().end.column, 6);
+    }
+
+    #[test]
+    fn bom_produces_same_tokens() {
+        assert_eq!(token_types("\u{FEFF}var x = 1;"), token_types("var x = 1;"));
+        assert_eq!(token_types("\u{FEFF}"), vec![TokenType::EOF]);
+        assert_eq!(token_types("\u{FEFF}#!/x\nprint 1;"), token_types("print 1;"));
+    }
+
+    #[test]
+    fn bom_keeps_offsets_but_restarts_columns() {
+        let source = "\u{FEFF}var x;";
+        let mut scanner = Scanner::new(source.to_string());
+        let token = scanner.scan_tokens()[0].clone();
+
+        assert_eq!(token.span().start, Position { line: 1, column: 1, offset: 3 });
+        assert_eq!(token.preceding_whitespace(source), "");
+        assert_eq!(scanner.source_byte_range(&token), Some("var"));
+    }
+
+    #[test]
+    fn bom_in_middle_of_file_is_not_skipped() {
+        let result = PanicFreeScanner::scan(String::from("var x;\u{FEFF}"));
+
+        assert_eq!(result.err(), Some(ScanError::InternalError(String::from("Invalid token"))));
+    }
+
+    #[test]
+    fn eof_token_is_empty_and_at_end_of_source() {
+        let mut scanner = Scanner::new(String::from("var x"));
+        let eof = scanner.scan_tokens().last().unwrap();
+
+        assert_eq!(eof.lexeme, "");
+        assert_eq!(eof.span().start, Position { line: 1, column: 6, offset: 5 });
+        assert_eq!(eof.span().start, eof.span().end);
+    }
+
+    #[test]
+    fn bang_and_bang_equal() {
+        assert_eq!(token_types("!a != b"), vec![
+            TokenType::BANG, TokenType::IDENTIFIER, TokenType::BANGEQUAL, TokenType::IDENTIFIER, TokenType::EOF
+        ]);
+        assert_eq!(token_types("!"), vec![TokenType::BANG, TokenType::EOF]);
+    }
+
+    #[test]
+    fn default_keywords_can_be_extended() {
+        let keywords = [KEYWORDS, &[("let", TokenType::VAR)]].concat();
+        let mut scanner = Scanner::with_keywords_from_slice(String::from("let x = nil;"), &keywords);
+        let types: Vec<TokenType> = scanner.scan_tokens().iter().map(|token| token.token_type.clone()).collect();
+
+        assert_eq!(types, vec![
+            TokenType::VAR, TokenType::IDENTIFIER, TokenType::EQUAL, TokenType::NIL, TokenType::SEMICOLON, TokenType::EOF
+        ]);
     }
 }
@@ -1,10 +1,141 @@
-use parser::Scanner;
-use std::io;
+use parser::{PanicFreeScanner, Repl};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::{env, fs, io::{self, Read, Write}, panic, path::Path, process, sync::mpsc, thread, time::Duration};
 
-fn main() {
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).expect("Couldn't read input");
+const WATCH_DEBOUNCE_MS: u64 = 50;
 
-    let mut scanner = Scanner::new(input);
-    scanner.scan_tokens().iter().for_each(|t| println!("{}", t))
+fn main() -> io::Result<()> {
+    // the scanner still reports errors by panicking; every mode reports the
+    // ScanError PanicFreeScanner turns that into, so keep the panic quiet
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut args = env::args().skip(1);
+    let mut from_stdin = false;
+    let mut eval_sources = Vec::new();
+    let mut check_path = None;
+    let mut watch_path = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stdin" => from_stdin = true,
+            "-e" | "--eval" => eval_sources.push(args.next().unwrap_or_else(|| usage())),
+            "--check" if check_path.is_none() => check_path = Some(args.next().unwrap_or_else(|| usage())),
+            "watch" | "--watch" if watch_path.is_none() => watch_path = Some(args.next().unwrap_or_else(|| usage())),
+            _ => usage()
+        }
+    }
+
+    let modes = [from_stdin, !eval_sources.is_empty(), check_path.is_some(), watch_path.is_some()];
+    if modes.iter().filter(|&&mode| mode).count() > 1 {
+        usage();
+    }
+
+    if let Some(path) = check_path {
+        check(&path);
+    }
+    else if let Some(path) = watch_path {
+        watch(&path);
+    }
+    else if from_stdin {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+
+        run(source);
+    }
+    else if !eval_sources.is_empty() {
+        run(eval_sources.join("\n"));
+    }
+    else {
+        Repl::new(io::stdin().lock(), io::stdout()).run()?;
+    }
+
+    Ok(())
+}
+
+fn run(source: String) {
+    match PanicFreeScanner::scan(source) {
+        Ok(tokens) => tokens.iter().for_each(|t| println!("{}", t)),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(65);
+        }
+    }
+}
+
+fn check(path: &str) {
+    let source = fs::read_to_string(path).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path, err);
+        process::exit(66);
+    });
+
+    if let Err(err) = PanicFreeScanner::scan(source) {
+        eprintln!("{}: {}", path, err);
+        process::exit(65);
+    }
+}
+
+fn watch(path: &str) -> ! {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).unwrap_or_else(|err| {
+        eprintln!("{}: {}", path, err);
+        process::exit(74);
+    });
+
+    // editors often save by writing a new file and renaming it over the old
+    // one, which a watch on the file itself would lose track of, so watch
+    // its directory and pick out the file's events
+    let file = Path::new(path);
+    let directory = file.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+
+    if let Err(err) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+        eprintln!("{}: {}", path, err);
+        process::exit(66);
+    }
+
+    rescan(path);
+
+    loop {
+        let Ok(event) = events.recv() else {
+            eprintln!("{}: file watcher stopped", path);
+            process::exit(74);
+        };
+
+        match event {
+            Ok(event) if is_change_to(&event, file) => {
+                // a save usually arrives as several events, so let them
+                // settle and rescan once
+                thread::sleep(Duration::from_millis(WATCH_DEBOUNCE_MS));
+                while events.try_recv().is_ok() {}
+
+                rescan(path);
+            },
+            Ok(_) => {},
+            Err(err) => eprintln!("{}: {}", path, err)
+        }
+    }
+}
+
+// a missing file is usually an editor replacing it on save, so removals are
+// ignored and the rescan waits for the new file to be created
+fn is_change_to(event: &Event, file: &Path) -> bool {
+    matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+        && event.paths.iter().any(|changed| changed.file_name() == file.file_name())
+}
+
+fn rescan(path: &str) {
+    // clear the terminal and move the cursor to the top left; errors go to
+    // stderr, so flush this first or it would only show up with more output
+    print!("\x1B[2J\x1B[1;1H");
+    let _ = io::stdout().flush();
+
+    match fs::read_to_string(path).map(PanicFreeScanner::scan) {
+        Ok(Ok(tokens)) => tokens.iter().for_each(|t| println!("{}", t)),
+        Ok(Err(err)) => eprintln!("{}: {}", path, err),
+        Err(err) => eprintln!("{}: {}", path, err)
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: cookielang [--stdin | -e <source>... | --check <file> | watch <file>]");
+    process::exit(64);
 }
@@ -0,0 +1,93 @@
+use std::io::{self, BufRead, Write};
+
+use crate::PanicFreeScanner;
+
+pub struct Repl<R: BufRead, W: Write> {
+    input: R,
+    output: W
+}
+
+impl<R: BufRead, W: Write> Repl<R, W> {
+    pub fn new(input: R, output: W) -> Repl<R, W> {
+        Repl { input, output }
+    }
+
+    pub fn run(&mut self) -> io::Result<()> {
+        loop {
+            write!(self.output, "> ")?;
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                break;
+            }
+
+            self.run_line(line)?;
+        }
+
+        Ok(())
+    }
+
+    // a line that fails to scan is reported and the session carries on
+    fn run_line(&mut self, source: String) -> io::Result<()> {
+        match PanicFreeScanner::scan(source) {
+            Ok(tokens) => for token in &tokens {
+                writeln!(self.output, "{}", token)?;
+            },
+            Err(err) => writeln!(self.output, "{}", err)?
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn run_session(input: &str) -> String {
+        let mut output = Vec::new();
+        Repl::new(Cursor::new(input), &mut output).run().unwrap();
+
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn prompts_once_per_line_and_at_end_of_input() {
+        let output = run_session("var x;\nprint x;\n");
+
+        assert_eq!(output.matches("> ").count(), 3);
+        assert!(output.ends_with("> "));
+    }
+
+    #[test]
+    fn scans_each_line_separately() {
+        let output = run_session("var x;\nprint x;\n");
+        let turns: Vec<&str> = output.split("> ").collect();
+
+        assert_eq!(turns.len(), 4);
+        assert!(turns[1].contains("Token Type: VAR") && !turns[1].contains("Token Type: PRINT"));
+        assert!(turns[2].contains("Token Type: PRINT") && !turns[2].contains("Token Type: VAR"));
+        assert_eq!(turns[1].matches("Token Type: EOF").count(), 1);
+        assert_eq!(turns[2].matches("Token Type: EOF").count(), 1);
+        assert_eq!(turns[3], "");
+    }
+
+    #[test]
+    fn empty_input_only_prompts() {
+        assert_eq!(run_session(""), "> ");
+    }
+
+    #[test]
+    fn scan_error_is_reported_and_session_continues() {
+        let output = run_session("var x;\n@\nprint x;\n");
+        let turns: Vec<&str> = output.split("> ").collect();
+
+        assert_eq!(turns.len(), 5);
+        assert!(turns[1].contains("Token Type: VAR"));
+        assert_eq!(turns[2], "Scanner error: Invalid token\n");
+        assert!(turns[3].contains("Token Type: PRINT"));
+        assert_eq!(turns[4], "");
+    }
+}
@@ -0,0 +1,156 @@
+// Lines and columns are 1-based and counted the way the scanner counts them
+// for Position: a tab advances the column by tab_width, every other char by
+// one, and columns on the first line start after a BOM.
+pub struct SourceView<'a> {
+    source: &'a str,
+    newline_offsets: Vec<usize>,
+    tab_width: usize
+}
+
+impl<'a> SourceView<'a> {
+    pub fn new(source: &'a str) -> SourceView<'a> {
+        let newline_offsets = source.match_indices('\n').map(|(offset, _)| offset).collect();
+
+        SourceView { source, newline_offsets, tab_width: 1 }
+    }
+
+    pub fn with_tab_width(mut self, tab_width: usize) -> SourceView<'a> {
+        self.tab_width = tab_width;
+        self
+    }
+
+    pub fn line_col(&self, byte_offset: usize) -> (usize, usize) {
+        let line_index = self.newline_offsets.partition_point(|&newline| newline < byte_offset);
+        let line_start = self.line_start(line_index);
+
+        let column = self.columns(line_index)
+            .take_while(|&(i, _, _)| line_start + i < byte_offset)
+            .last()
+            .map_or(1, |(_, c, column)| column + self.width(c));
+
+        (line_index + 1, column)
+    }
+
+    // a column inside a tab maps to the tab itself
+    pub fn byte_offset(&self, line: usize, column: usize) -> usize {
+        let line_index = line.saturating_sub(1);
+        let line_start = self.line_start(line_index);
+        let line_end = self.line_end(line_index);
+
+        self.columns(line_index)
+            .find(|&(_, c, start)| column < start + self.width(c))
+            .map_or(line_end, |(i, _, _)| line_start + i)
+    }
+
+    // each char on the line with its byte index from the line start and the
+    // column it starts at
+    fn columns(&self, line_index: usize) -> impl Iterator<Item = (usize, char, usize)> + '_ {
+        let line_start = self.line_start(line_index);
+        let mut column = 1;
+
+        self.source[line_start..self.line_end(line_index)].char_indices()
+            .filter(move |&(i, c)| !(line_start + i == 0 && c == '\u{FEFF}'))
+            .map(move |(i, c)| {
+                let start = column;
+                column += self.width(c);
+                (i, c, start)
+            })
+    }
+
+    fn width(&self, c: char) -> usize {
+        if c == '\t' { self.tab_width } else { 1 }
+    }
+
+    fn line_start(&self, line_index: usize) -> usize {
+        match line_index {
+            0 => 0,
+            _ => self.newline_offsets.get(line_index - 1).map_or(self.source.len(), |newline| newline + 1)
+        }
+    }
+
+    fn line_end(&self, line_index: usize) -> usize {
+        self.newline_offsets.get(line_index).copied().unwrap_or(self.source.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Scanner;
+
+    fn assert_round_trips(view: &SourceView, source: &str) {
+        // a leading BOM has no column of its own to come back to
+        for (offset, _) in source.char_indices().filter(|&(offset, c)| !(offset == 0 && c == '\u{FEFF}')) {
+            let (line, column) = view.line_col(offset);
+            assert_eq!(view.byte_offset(line, column), offset, "offset {} at {}:{}", offset, line, column);
+        }
+    }
+
+    #[test]
+    fn line_col_round_trips() {
+        let source = "var a = 1;\n\nprint a;\n";
+        let view = SourceView::new(source);
+
+        assert_eq!(view.line_col(0), (1, 1));
+        assert_eq!(view.line_col(4), (1, 5));
+        assert_eq!(view.line_col(11), (2, 1));
+        assert_eq!(view.line_col(12), (3, 1));
+        assert_eq!(view.line_col(18), (3, 7));
+        assert_round_trips(&view, source);
+    }
+
+    #[test]
+    fn columns_count_chars_not_bytes() {
+        let source = "\"héllo\" + \"π\";\n\"日本\"";
+        let view = SourceView::new(source);
+
+        assert_eq!(view.line_col(source.find('+').unwrap()), (1, 9));
+        assert_eq!(view.line_col(source.find('π').unwrap()), (1, 12));
+        assert_eq!(view.line_col(source.find('本').unwrap()), (2, 3));
+        assert_round_trips(&view, source);
+    }
+
+    #[test]
+    fn tabs_advance_by_tab_width() {
+        let source = "\tif\t(x)";
+        let view = SourceView::new(source).with_tab_width(4);
+
+        assert_eq!(view.line_col(1), (1, 5));
+        assert_eq!(view.line_col(4), (1, 11));
+        assert_eq!(view.byte_offset(1, 3), 0);
+        assert_round_trips(&view, source);
+    }
+
+    #[test]
+    fn first_line_columns_start_after_bom() {
+        let source = "\u{FEFF}ab\ncd";
+        let view = SourceView::new(source);
+
+        assert_eq!(view.line_col(3), (1, 1));
+        assert_eq!(view.byte_offset(1, 1), 3);
+        assert_eq!(view.line_col(source.find('d').unwrap()), (2, 2));
+        assert_round_trips(&view, source);
+    }
+
+    #[test]
+    fn past_end_of_line_clamps_to_line_end() {
+        let view = SourceView::new("ab\ncd");
+
+        assert_eq!(view.byte_offset(1, 10), 2);
+        assert_eq!(view.byte_offset(2, 10), 5);
+    }
+
+    #[test]
+    fn agrees_with_scanner_positions() {
+        let source = "\u{FEFF}var\tname = \"é\tx\";\n\tprint name;";
+        let mut scanner = Scanner::new(source.to_string()).with_tab_width(4);
+        let tokens = scanner.scan_tokens().clone();
+        let view = scanner.as_source_view();
+
+        for token in &tokens {
+            let start = token.span().start;
+            assert_eq!(view.line_col(start.offset), (start.line, start.column));
+            assert_eq!(view.byte_offset(start.line, start.column), start.offset);
+        }
+    }
+}
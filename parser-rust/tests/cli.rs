@@ -0,0 +1,159 @@
+use std::{fs, io::{Read, Write}, path::PathBuf, process::{Command, Output, Stdio}, sync::{Arc, Mutex}, thread, time::{Duration, Instant}};
+
+fn cookielang(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_parser"))
+        .args(args)
+        .output()
+        .expect("failed to run cookielang")
+}
+
+fn write_script(name: &str, source: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("cookielang-{}-{}.cookie", std::process::id(), name));
+    fs::write(&path, source).unwrap();
+    path
+}
+
+#[test]
+fn check_valid_file_exits_0() {
+    let path = write_script("check-valid", "var x = 1;\nprint x;\n");
+    let output = cookielang(&["--check", path.to_str().unwrap()]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn check_invalid_file_exits_65_and_names_file() {
+    let path = write_script("check-invalid", "var @;\n");
+    let output = cookielang(&["--check", path.to_str().unwrap()]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stderr, format!("{}: Scanner error: Invalid token\n", path.display()));
+}
+
+#[test]
+fn check_missing_file_exits_66() {
+    let output = cookielang(&["--check", "/nonexistent/missing.cookie"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(66));
+    assert!(stderr.starts_with("/nonexistent/missing.cookie: "));
+    assert!(!stderr.contains("Os {"));
+}
+
+#[test]
+fn eval_prints_tokens() {
+    let output = cookielang(&["-e", "print 1;"]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout.matches("Token Type:").count(), 4);
+    assert!(stdout.starts_with("Token Type: PRINT,"));
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn eval_joins_multiple_sources_with_newlines() {
+    let joined = cookielang(&["-e", "var a = 1;", "--eval", "print a;"]);
+    let single = cookielang(&["-e", "var a = 1;\nprint a;"]);
+
+    assert_eq!(joined.status.code(), Some(0));
+    assert_eq!(joined.stdout, single.stdout);
+}
+
+#[test]
+fn eval_scan_error_exits_65_without_panicking() {
+    let output = cookielang(&["-e", "\"unterminated"]);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert_eq!(output.status.code(), Some(65));
+    assert_eq!(stderr, "Scanner error: Undetermined string\n");
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn stdin_scans_all_input_without_prompting() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_parser"))
+        .arg("--stdin")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to run cookielang");
+
+    child.stdin.take().unwrap().write_all(b"print 1+1;\nprint 2;\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!stdout.starts_with("> "));
+    assert_eq!(stdout.matches("Token Type:").count(), 9);
+    assert!(stdout.contains("Line: 2,"));
+}
+
+const CLEAR_SCREEN: &str = "\x1B[2J\x1B[1;1H";
+
+// collects everything a child writes to a pipe so a test can wait on it
+fn collect(mut pipe: impl Read + Send + 'static) -> Arc<Mutex<String>> {
+    let collected = Arc::new(Mutex::new(String::new()));
+    let writer = Arc::clone(&collected);
+
+    thread::spawn(move || {
+        let mut buffer = [0; 1024];
+        while let Ok(read) = pipe.read(&mut buffer) {
+            if read == 0 {
+                break;
+            }
+            writer.lock().unwrap().push_str(&String::from_utf8_lossy(&buffer[..read]));
+        }
+    });
+
+    collected
+}
+
+fn wait_for(collected: &Arc<Mutex<String>>, done: impl Fn(&str) -> bool) -> String {
+    let deadline = Instant::now() + Duration::from_secs(10);
+
+    loop {
+        let output = collected.lock().unwrap().clone();
+        if done(&output) || Instant::now() > deadline {
+            return output;
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn watch_rescans_when_the_file_changes() {
+    let path = write_script("watch", "var x;\n");
+    let mut child = Command::new(env!("CARGO_BIN_EXE_parser"))
+        .args(["watch", path.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run cookielang");
+    let stdout = collect(child.stdout.take().unwrap());
+    let stderr = collect(child.stderr.take().unwrap());
+
+    let first = wait_for(&stdout, |output| output.contains("Token Type: EOF"));
+    fs::write(&path, "print 1;\n").unwrap();
+    let second = wait_for(&stdout, |output| output.rsplit(CLEAR_SCREEN).next().unwrap().contains("Token Type: EOF")
+        && output.matches(CLEAR_SCREEN).count() >= 2);
+    fs::write(&path, "var @;\n").unwrap();
+    let errors = wait_for(&stderr, |output| output.contains("Invalid token"));
+    let third = stdout.lock().unwrap().clone();
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(first.starts_with(CLEAR_SCREEN));
+    assert!(first.contains("Token Type: VAR"));
+
+    let latest = second.rsplit(CLEAR_SCREEN).next().unwrap();
+    assert!(latest.contains("Token Type: PRINT") && !latest.contains("Token Type: VAR"));
+
+    assert_eq!(errors.lines().next(), Some(format!("{}: Scanner error: Invalid token", path.display()).as_str()));
+    assert!(third.ends_with(CLEAR_SCREEN));
+}